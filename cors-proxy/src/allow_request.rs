@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use axum::http::Request;
 use serde_qs::from_str;
 
+use crate::config::Config;
+
 struct Url<'l> {
     pub pathname: &'l str,
     pub query: HashMap<String, String>,
@@ -45,21 +47,29 @@ fn is_info_refs<B>(req: &Request<B>, u: &Url) -> bool {
         .map(|s| s == "git-upload-pack" || s == "git-receive-pack").unwrap_or(false))
 }
 
+/// a preflight is only worth answering if it's asking permission for a header the
+/// actual request will send; `git-protocol` carries the client's wire protocol v2
+/// negotiation and must be allowed through the same as `content-type`
+fn requests_forwarded_header<B>(req: &Request<B>) -> bool {
+    req.header_contains("access-control-request-headers", "content-type")
+        || req.header_contains("access-control-request-headers", "git-protocol")
+}
+
 fn is_preflight_pull<B>(req: &Request<B>) -> bool {
     req.method() == "OPTIONS"
-        && req.headers().get("access-control-request-headers").unwrap().to_str().unwrap_or("").contains("content-type")
+        && requests_forwarded_header(req)
         && req.uri().path().ends_with("git-upload-pack")
 }
 
 fn is_pull<B>(req: &Request<B>) -> bool {
     req.method() == "POST"
-        && req.headers().get("content-type").unwrap() == "application/x-git-upload-pack-request"
+        && req.header_matches("content-type", &|s| s == "application/x-git-upload-pack-request")
         && req.uri().path().ends_with("git-upload-pack")
 }
 
 fn is_preflight_push<B>(req: &Request<B>) -> bool {
     req.method() == "OPTIONS"
-        && req.header_contains("access-control-request-headers", "content-type")
+        && requests_forwarded_header(req)
         && req.uri().path().ends_with("git-receive-pack")
 }
 
@@ -70,21 +80,63 @@ fn is_push<B>(req: &Request<B>) -> bool {
         && req.uri().path().ends_with("git-receive-pack")
 }
 
+fn is_preflight_lfs_batch<B>(req: &Request<B>) -> bool {
+    req.method() == "OPTIONS"
+        && requests_forwarded_header(req)
+        && req.uri().path().ends_with("/info/lfs/objects/batch")
+}
+
+fn is_lfs_batch<B>(req: &Request<B>) -> bool {
+    req.method() == "POST"
+        && req.header_matches("content-type", &|s| s == "application/vnd.git-lfs+json")
+        && req.uri().path().ends_with("/info/lfs/objects/batch")
+}
+
+/// extracts the `scheme://host/...` portion embedded in the request path, e.g.
+/// `/https://github.com/user/repo.git/info/refs` -> `https://github.com/user/repo.git/info/refs`
+pub fn upstream_target(path: &str) -> Option<&str> {
+    let upstream = path.strip_prefix('/')?;
+    if upstream.starts_with("http://") || upstream.starts_with("https://") {
+        Some(upstream)
+    } else {
+        None
+    }
+}
+
+fn upstream_host(path: &str) -> Option<&str> {
+    let (_, after_scheme) = upstream_target(path)?.split_once("://")?;
+    let host = after_scheme.split(['/', '?']).next()?;
+    if host.is_empty() { None } else { Some(host) }
+}
+
+fn origin_allowed<B>(req: &Request<B>, config: &Config) -> bool {
+    match req.headers().get("origin").map(|h| h.to_str().ok()).flatten() {
+        Some(origin) => config.allows_origin(origin),
+        None => config.allowed_origins.is_empty(),
+    }
+}
+
 #[allow(dead_code)]
-pub fn allow<B>(req: &Request<B>) -> bool {
+pub fn allow<B>(req: &Request<B>, config: &Config) -> bool {
     let u = &Url::new(&req);
-    return is_preflight_info_refs(req, u) ||
+    let route_allowed = is_preflight_info_refs(req, u) ||
         is_info_refs(req, u) ||
         is_preflight_pull(req) ||
         is_pull(req) ||
         is_preflight_push(req) ||
-        is_push(req);
+        is_push(req) ||
+        is_preflight_lfs_batch(req) ||
+        is_lfs_batch(req);
+
+    route_allowed
+        && origin_allowed(req, config)
+        && upstream_host(u.pathname).map(|host| config.allows_host(host)).unwrap_or(false)
 }
 
 #[cfg(test)]
 mod tests {
     use axum::http::{Method, Uri};
-    use maplit::hashmap;
+    use maplit::{hashmap, hashset};
 
     use super::*;
 
@@ -183,12 +235,24 @@ mod tests {
         assert_eq!(is_preflight_pull(&req), true);
     }
 
+    #[test]
+    fn test_is_preflight_pull_accepts_git_protocol_header() {
+        let req = build_request(Method::OPTIONS, "/test/git-upload-pack", Some(hashmap!["access-control-request-headers" => "git-protocol"].to_string_map()));
+        assert_eq!(is_preflight_pull(&req), true);
+    }
+
     #[test]
     fn test_is_pull() {
         let req = build_request(Method::POST, "/test/git-upload-pack", Some(hashmap!["Content-Type" => "application/x-git-upload-pack-request"].to_string_map()));
         assert_eq!(is_pull(&req), true);
     }
 
+    #[test]
+    fn test_is_pull_returns_false_without_content_type_header_instead_of_panicking() {
+        let req = build_request(Method::POST, "/test/git-upload-pack", None);
+        assert_eq!(is_pull(&req), false);
+    }
+
     #[test]
     fn test_allow_preflight_push() {
         let req = build_request(Method::OPTIONS, "/test/git-receive-pack", Some(hashmap!["Content-Type" => "application/json", "Access-Control-Request-Headers" => "content-type"].to_string_map()));
@@ -200,4 +264,63 @@ mod tests {
         let req = build_request(Method::POST, "/test/git-receive-pack", Some(hashmap!["Content-Type" => "application/x-git-receive-pack-request"].to_string_map()));
         assert_eq!(is_push(&req), true);
     }
+
+    #[test]
+    fn test_is_preflight_lfs_batch() {
+        let req = build_request(Method::OPTIONS, "/test/info/lfs/objects/batch", Some(hashmap!["access-control-request-headers" => "content-type"].to_string_map()));
+        assert_eq!(is_preflight_lfs_batch(&req), true);
+    }
+
+    #[test]
+    fn test_is_lfs_batch() {
+        let req = build_request(Method::POST, "/test/info/lfs/objects/batch", Some(hashmap!["Content-Type" => "application/vnd.git-lfs+json"].to_string_map()));
+        assert_eq!(is_lfs_batch(&req), true);
+    }
+
+    #[test]
+    fn test_upstream_host_extracts_host_from_path() {
+        assert_eq!(upstream_host("/https://github.com/user/repo.git/info/refs"), Some("github.com"));
+    }
+
+    #[test]
+    fn test_upstream_host_is_none_without_scheme() {
+        assert_eq!(upstream_host("/github.com/user/repo.git/info/refs"), None);
+    }
+
+    #[test]
+    fn test_allow_rejects_host_not_in_allowlist() {
+        let req = build_request(
+            Method::GET,
+            "/https://evil.example/test/info/refs?service=git-upload-pack",
+            None,
+        );
+        let config = Config { allowed_hosts: hashset!["github.com".to_string()], ..Config::default() };
+        assert_eq!(allow(&req, &config), false);
+    }
+
+    #[test]
+    fn test_allow_rejects_origin_not_in_allowlist() {
+        let req = build_request(
+            Method::GET,
+            "/https://github.com/test/info/refs?service=git-upload-pack",
+            Some(hashmap!["origin" => "https://evil.example"].to_string_map()),
+        );
+        let config = Config { allowed_origins: hashset!["https://trusted.example".to_string()], ..Config::default() };
+        assert_eq!(allow(&req, &config), false);
+    }
+
+    #[test]
+    fn test_allow_accepts_when_origin_and_host_are_allowed() {
+        let req = build_request(
+            Method::GET,
+            "/https://github.com/test/info/refs?service=git-upload-pack",
+            Some(hashmap!["origin" => "https://trusted.example"].to_string_map()),
+        );
+        let config = Config {
+            allowed_origins: hashset!["https://trusted.example".to_string()],
+            allowed_hosts: hashset!["github.com".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(allow(&req, &config), true);
+    }
 }
@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+const CONFIG_PATH_ENV: &str = "CORS_PROXY_CONFIG";
+const ALLOWED_ORIGINS_ENV: &str = "CORS_PROXY_ALLOWED_ORIGINS";
+const ALLOWED_HOSTS_ENV: &str = "CORS_PROXY_ALLOWED_HOSTS";
+const AUTH_SECRETS_ENV: &str = "CORS_PROXY_AUTH_SECRETS";
+const AUTH_SKEW_SECONDS_ENV: &str = "CORS_PROXY_AUTH_SKEW_SECONDS";
+const CONNECT_TIMEOUT_SECONDS_ENV: &str = "CORS_PROXY_CONNECT_TIMEOUT_SECONDS";
+const CLIENT_BODY_TIMEOUT_SECONDS_ENV: &str = "CORS_PROXY_CLIENT_BODY_TIMEOUT_SECONDS";
+const UPSTREAM_TIMEOUT_SECONDS_ENV: &str = "CORS_PROXY_UPSTREAM_TIMEOUT_SECONDS";
+const TLS_CERT_PATH_ENV: &str = "CORS_PROXY_TLS_CERT_PATH";
+const TLS_KEY_PATH_ENV: &str = "CORS_PROXY_TLS_KEY_PATH";
+
+const DEFAULT_AUTH_SKEW_SECONDS: u64 = 300;
+const DEFAULT_CONNECT_TIMEOUT_SECONDS: u64 = 10;
+const DEFAULT_CLIENT_BODY_TIMEOUT_SECONDS: u64 = 30;
+const DEFAULT_UPSTREAM_TIMEOUT_SECONDS: u64 = 60;
+
+/// the allowlists that gate `allow_request::allow`; an empty set means "no restriction",
+/// which keeps the proxy's previous unconditional behavior when nothing is configured.
+/// `auth_secrets` gates `middleware::auth` the same way: empty means the layer is disabled.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub allowed_origins: HashSet<String>,
+    pub allowed_hosts: HashSet<String>,
+    pub auth_secrets: Vec<String>,
+    pub auth_skew_seconds: u64,
+    /// how long to wait for the TCP/TLS handshake with the upstream
+    pub connect_timeout_seconds: u64,
+    /// how long to wait for each chunk of the client's request body (the whole body
+    /// may take longer); exceeding this yields a 408, since the client is the one stalling
+    pub client_body_timeout_seconds: u64,
+    /// overall deadline for the upstream to respond (connect + send + receive);
+    /// exceeding this yields a 504, since the upstream is the one stalling
+    pub upstream_timeout_seconds: u64,
+    /// when both are set, `main` serves over TLS instead of plain HTTP
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            allowed_origins: HashSet::new(),
+            allowed_hosts: HashSet::new(),
+            auth_secrets: Vec::new(),
+            auth_skew_seconds: DEFAULT_AUTH_SKEW_SECONDS,
+            connect_timeout_seconds: DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            client_body_timeout_seconds: DEFAULT_CLIENT_BODY_TIMEOUT_SECONDS,
+            upstream_timeout_seconds: DEFAULT_UPSTREAM_TIMEOUT_SECONDS,
+            tls_cert_path: None,
+            tls_key_path: None,
+        }
+    }
+}
+
+impl Config {
+    /// loads `CORS_PROXY_CONFIG` as a TOML file if set, then layers env vars on top so
+    /// deployments can mix a static file with secrets/env: comma-separated origins, hosts
+    /// and secrets are added to the file's lists, while the other values replace the file's.
+    /// an unset env var means "not configured" and falls back to `Config::default()`,
+    /// but a set-and-unreadable/unparseable one must fail loudly instead of silently
+    /// falling back to the same unrestricted default, which would reopen the relay
+    fn load() -> Config {
+        let mut config = match env::var(CONFIG_PATH_ENV) {
+            Err(_) => Config::default(),
+            Ok(path) => {
+                let contents = fs::read_to_string(&path)
+                    .unwrap_or_else(|err| panic!("{CONFIG_PATH_ENV} set to {path:?} but failed to read it: {err}"));
+                toml::from_str(&contents)
+                    .unwrap_or_else(|err| panic!("{CONFIG_PATH_ENV} set to {path:?} but failed to parse it as TOML: {err}"))
+            }
+        };
+
+        if let Ok(origins) = env::var(ALLOWED_ORIGINS_ENV) {
+            config.allowed_origins.extend(split_csv(&origins));
+        }
+        if let Ok(hosts) = env::var(ALLOWED_HOSTS_ENV) {
+            config.allowed_hosts.extend(split_csv(&hosts));
+        }
+        if let Ok(secrets) = env::var(AUTH_SECRETS_ENV) {
+            config.auth_secrets.extend(split_csv(&secrets));
+        }
+        if let Ok(skew) = env::var(AUTH_SKEW_SECONDS_ENV) {
+            if let Ok(skew) = skew.parse() {
+                config.auth_skew_seconds = skew;
+            }
+        }
+        if let Ok(timeout) = env::var(CONNECT_TIMEOUT_SECONDS_ENV) {
+            if let Ok(timeout) = timeout.parse() {
+                config.connect_timeout_seconds = timeout;
+            }
+        }
+        if let Ok(timeout) = env::var(CLIENT_BODY_TIMEOUT_SECONDS_ENV) {
+            if let Ok(timeout) = timeout.parse() {
+                config.client_body_timeout_seconds = timeout;
+            }
+        }
+        if let Ok(timeout) = env::var(UPSTREAM_TIMEOUT_SECONDS_ENV) {
+            if let Ok(timeout) = timeout.parse() {
+                config.upstream_timeout_seconds = timeout;
+            }
+        }
+        if let Ok(path) = env::var(TLS_CERT_PATH_ENV) {
+            config.tls_cert_path = Some(path);
+        }
+        if let Ok(path) = env::var(TLS_KEY_PATH_ENV) {
+            config.tls_key_path = Some(path);
+        }
+
+        config
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.is_empty() || self.allowed_origins.contains(origin)
+    }
+
+    pub fn allows_host(&self, host: &str) -> bool {
+        self.allowed_hosts.is_empty() || self.allowed_hosts.contains(host)
+    }
+}
+
+fn split_csv(value: &str) -> HashSet<String> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+pub static CONFIG: Lazy<Config> = Lazy::new(Config::load);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows_origin_is_unrestricted_when_empty() {
+        let config = Config::default();
+        assert_eq!(config.allows_origin("https://example.com"), true);
+    }
+
+    #[test]
+    fn test_allows_origin_matches_configured_set() {
+        let config = Config { allowed_origins: split_csv("https://example.com"), ..Config::default() };
+        assert_eq!(config.allows_origin("https://example.com"), true);
+        assert_eq!(config.allows_origin("https://evil.example"), false);
+    }
+
+    #[test]
+    fn test_allows_host_is_unrestricted_when_empty() {
+        let config = Config::default();
+        assert_eq!(config.allows_host("github.com"), true);
+    }
+
+    #[test]
+    fn test_allows_host_matches_configured_set() {
+        let config = Config { allowed_hosts: split_csv("github.com,gitlab.com"), ..Config::default() };
+        assert_eq!(config.allows_host("github.com"), true);
+        assert_eq!(config.allows_host("evil.example"), false);
+    }
+}
@@ -1,33 +1,51 @@
 mod allow_request;
+mod config;
 mod middleware;
+mod proxy;
 
 use std::net::SocketAddr;
 
-use axum::{Router, routing::get};
-use axum::http::StatusCode;
+use axum::Router;
+use axum::middleware::from_fn;
+use axum::routing::any;
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::CONFIG;
 
 //noinspection HttpUrlsUsage
 #[tokio::main]
 async fn main() {
     let app = router();
-
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    let server = axum::Server::bind(&addr)
-        .serve(app.into_make_service());
-
-    println!("Listening on http://{}", server.local_addr());
-    tokio::spawn(async move { server.await.unwrap(); }).await.unwrap();
 
+    match (&CONFIG.tls_cert_path, &CONFIG.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let tls_config = RustlsConfig::from_pem_file(cert_path, key_path).await
+                .expect("failed to load TLS certificate/key");
+
+            println!("Listening on https://{addr}");
+            axum_server::bind_rustls(addr, tls_config)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+        _ => {
+            let server = axum::Server::bind(&addr)
+                .serve(app.into_make_service());
+
+            println!("Listening on http://{}", server.local_addr());
+            tokio::spawn(async move { server.await.unwrap(); }).await.unwrap();
+        }
+    }
 }
 
 fn router() -> Router {
     Router::new()
-        .route("/", get(process_request))
-        .route("/*any", get(|| async { StatusCode::FORBIDDEN }))
-}
-
-async fn process_request() -> &'static str {
-    "Hello, World!"
+        // matchit's `/*any` wildcard doesn't match the bare root path, so it needs its
+        // own route to go through the same allow()/403 gate as everything else
+        .route("/", any(proxy::process_request))
+        .route("/*any", any(proxy::process_request))
+        .layer(from_fn(middleware::auth))
 }
 
 #[cfg(test)]
@@ -63,6 +81,22 @@ mod test {
         assert_eq!(res.status(), StatusCode::FORBIDDEN);
     }
 
+    #[tokio::test]
+    async fn test_403_on_root() {
+        let context = setup_server().await;
+        let uri = format!("http://{}/", context.uri.authority().unwrap())
+            .parse::<Uri>()
+            .unwrap();
+        let req = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+
+        let res = Client::new().request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
     async fn setup_server() -> Test {
         let any_port = SocketAddr::from(([127, 0, 0, 1], 0));
         let server = Server::bind(&any_port)
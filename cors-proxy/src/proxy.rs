@@ -0,0 +1,250 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::Duration;
+
+use axum::body::{Body, StreamBody};
+use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use once_cell::sync::Lazy;
+use reqwest::redirect::Policy;
+use tokio_stream::StreamExt;
+
+use crate::allow_request::{allow, upstream_target};
+use crate::config::CONFIG;
+
+/// identifies us to upstreams with a fixed `cors-proxy/<version>` user agent; the
+/// browser's own `User-Agent` is not forwarded
+const USER_AGENT: &str = concat!("cors-proxy/", env!("CARGO_PKG_VERSION"));
+
+/// response headers we trust upstreams to set safely and copy back to the browser;
+/// anything not in this list (cookies, upstream CORS headers, etc) is dropped.
+/// `git-protocol` must stay in sync with `EXPOSED_RESPONSE_HEADERS` below, or the
+/// expose-headers declaration advertises a header that never actually arrives
+const FORWARDED_RESPONSE_HEADERS: &[&str] = &["content-type", "content-encoding", "git-protocol"];
+
+/// request headers we pass through to the upstream; `authorization` carries LFS batch
+/// credentials and `git-protocol` carries the client's wire protocol v2 negotiation, so
+/// both must also be allowed through preflight (see `ALLOWED_REQUEST_HEADERS`)
+const FORWARDED_REQUEST_HEADERS: &[&str] = &["content-type", "authorization", "git-protocol"];
+
+/// advertised via `Access-Control-Allow-Headers` so browsers let these through preflight;
+/// `x-proxy-signature`/`x-proxy-timestamp` are consumed by `middleware::auth` itself
+/// (not forwarded upstream), but the browser still needs permission to set them
+const ALLOWED_REQUEST_HEADERS: &str =
+    "content-type, authorization, git-protocol, x-proxy-signature, x-proxy-timestamp";
+
+/// advertised via `Access-Control-Expose-Headers` so the client can read `git-protocol`
+/// back off the proxied response
+const EXPOSED_RESPONSE_HEADERS: &str = "git-protocol";
+
+/// reqwest recommends building a single client and reusing it for connection pooling;
+/// redirects are disabled so git's dumb-http 301/302 responses reach the browser instead
+/// of being followed (and leaking) on the proxy's behalf
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .redirect(Policy::none())
+        .connect_timeout(Duration::from_secs(CONFIG.connect_timeout_seconds))
+        .build()
+        .expect("failed to build upstream http client")
+});
+
+/// marks a body-stream error as "the client stalled sending its request", so it can be
+/// told apart from a reqwest timeout (which fires for a stalled upstream instead) after
+/// reqwest re-wraps it on its way back out of `upstream_req.send()`
+#[derive(Debug)]
+struct ClientBodyTimeout;
+
+impl fmt::Display for ClientBodyTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out waiting for the client to send its request body")
+    }
+}
+
+impl StdError for ClientBodyTimeout {}
+
+fn is_client_body_timeout(err: &reqwest::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err.downcast_ref::<ClientBodyTimeout>().is_some() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// the path is expected to look like `/https://github.com/user/repo.git/info/refs`,
+/// i.e. the upstream url with its scheme inlined right after the leading slash
+fn upstream_url(path: &str, query: Option<&str>) -> Option<String> {
+    let upstream = upstream_target(path)?;
+    match query {
+        Some(query) if !query.is_empty() => Some(format!("{upstream}?{query}")),
+        _ => Some(upstream.to_string()),
+    }
+}
+
+/// echoes back the specific allowed origin that made the request, rather than a blanket `*`,
+/// so that distinct whitelisted front-ends can each be allowed; `*` is sent only when the
+/// request has no `Origin` and no origin allowlist is configured.
+/// `access-control-allow-credentials` is not set
+fn cors_headers<B>(req: &Request<B>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("vary", HeaderValue::from_static("origin"));
+    headers.insert("access-control-expose-headers", HeaderValue::from_static(EXPOSED_RESPONSE_HEADERS));
+    if let Some(origin) = req.headers().get("origin") {
+        if CONFIG.allows_origin(origin.to_str().unwrap_or_default()) {
+            headers.insert("access-control-allow-origin", origin.clone());
+        }
+    } else if CONFIG.allowed_origins.is_empty() {
+        headers.insert("access-control-allow-origin", HeaderValue::from_static("*"));
+    }
+    headers
+}
+
+fn preflight_response<B>(req: &Request<B>) -> Response {
+    let mut res = StatusCode::NO_CONTENT.into_response();
+    res.headers_mut().extend(cors_headers(req));
+    let headers = res.headers_mut();
+    headers.insert("access-control-allow-methods", HeaderValue::from_static("GET, POST, OPTIONS"));
+    headers.insert("access-control-allow-headers", HeaderValue::from_static(ALLOWED_REQUEST_HEADERS));
+    res
+}
+
+pub async fn process_request(req: Request<Body>) -> Response {
+    if !allow(&req, &CONFIG) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    if req.method() == Method::OPTIONS {
+        return preflight_response(&req);
+    }
+
+    let Some(target) = upstream_url(req.uri().path(), req.uri().query()) else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+
+    let method = req.method().clone();
+    let forwarded_headers: Vec<_> = FORWARDED_REQUEST_HEADERS.iter()
+        .filter_map(|name| req.headers().get(*name).map(|value| (*name, value.clone())))
+        .collect();
+    let cors = cors_headers(&req);
+
+    // streamed straight through to the upstream so a multi-gigabyte push is never held
+    // in memory; the client's per-chunk timeout is threaded into the stream itself so a
+    // stalled *client* can still be told apart from a stalled upstream below, without
+    // ever buffering the body to measure elapsed time
+    let client_body_timeout = Duration::from_secs(CONFIG.client_body_timeout_seconds);
+    let body_stream = req.into_body().timeout(client_body_timeout).map(|chunk| match chunk {
+        Ok(Ok(bytes)) => Ok(bytes),
+        Ok(Err(err)) => Err(Box::new(err) as Box<dyn StdError + Send + Sync>),
+        Err(_) => Err(Box::new(ClientBodyTimeout) as Box<dyn StdError + Send + Sync>),
+    });
+
+    let mut upstream_req = CLIENT.request(method, &target)
+        .body(reqwest::Body::wrap_stream(body_stream))
+        .timeout(Duration::from_secs(CONFIG.upstream_timeout_seconds));
+    for (name, value) in forwarded_headers {
+        upstream_req = upstream_req.header(name, value);
+    }
+
+    // reqwest's own timeout covers connect + send + the full response body read, so a
+    // response that streams headers promptly and then stalls mid-body is still caught
+    let upstream_res = match upstream_req.send().await {
+        Ok(res) => res,
+        Err(err) if is_client_body_timeout(&err) => return StatusCode::REQUEST_TIMEOUT.into_response(),
+        Err(err) if err.is_timeout() => return StatusCode::GATEWAY_TIMEOUT.into_response(),
+        Err(_) => return StatusCode::BAD_GATEWAY.into_response(),
+    };
+
+    let mut res = Response::builder().status(upstream_res.status());
+    for name in FORWARDED_RESPONSE_HEADERS {
+        if let Some(value) = upstream_res.headers().get(*name) {
+            res = res.header(*name, value);
+        }
+    }
+    let mut res = res.body(StreamBody::new(upstream_res.bytes_stream())).unwrap().into_response();
+    res.headers_mut().extend(cors);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::routing::get;
+    use axum::Router;
+    use hyper::body::to_bytes;
+    use tokio::sync::oneshot::{channel, Sender};
+    use tokio::task;
+
+    use super::*;
+
+    /// when this is dropped, the sender shuts down the fake upstream server
+    struct FakeUpstream {
+        addr: SocketAddr,
+        _send_shutdown: Sender<()>,
+    }
+
+    async fn fake_upstream_handler() -> Response {
+        let mut res = "the answer is 42".into_response();
+        res.headers_mut().insert("git-protocol", HeaderValue::from_static("version=2"));
+        res.headers_mut().insert("set-cookie", HeaderValue::from_static("session=leaked"));
+        res
+    }
+
+    async fn spawn_fake_upstream() -> FakeUpstream {
+        let any_port = SocketAddr::from(([127, 0, 0, 1], 0));
+        let server = axum::Server::bind(&any_port)
+            .serve(Router::new().route("/*any", get(fake_upstream_handler)).into_make_service());
+
+        let addr = server.local_addr();
+        let (tx, rx): (Sender<()>, _) = channel();
+        task::spawn(async move {
+            server.with_graceful_shutdown(async { rx.await.ok(); }).await.unwrap();
+        });
+
+        FakeUpstream { addr, _send_shutdown: tx }
+    }
+
+    #[tokio::test]
+    async fn test_process_request_forwards_request_and_response_through_to_upstream() {
+        let upstream = spawn_fake_upstream().await;
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(format!("/http://{}/test/info/refs?service=git-upload-pack", upstream.addr))
+            .body(Body::empty())
+            .unwrap();
+
+        let res = process_request(req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        // forwarded because it's on the allowlist, and allows the client to read it back
+        assert_eq!(res.headers().get("git-protocol").unwrap(), "version=2");
+        // not on the allowlist, so must not be copied through from the upstream
+        assert_eq!(res.headers().get("set-cookie"), None);
+        let body = to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"the answer is 42");
+    }
+
+    #[test]
+    fn test_upstream_url_with_scheme_and_query() {
+        let url = upstream_url(
+            "/https://github.com/user/repo.git/info/refs",
+            Some("service=git-upload-pack"),
+        );
+        assert_eq!(url, Some("https://github.com/user/repo.git/info/refs?service=git-upload-pack".to_string()));
+    }
+
+    #[test]
+    fn test_upstream_url_without_query() {
+        let url = upstream_url("/http://example.com/repo.git/info/refs", None);
+        assert_eq!(url, Some("http://example.com/repo.git/info/refs".to_string()));
+    }
+
+    #[test]
+    fn test_upstream_url_rejects_path_without_scheme() {
+        let url = upstream_url("/example", None);
+        assert_eq!(url, None);
+    }
+}